@@ -1,82 +1,242 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use anyhow::anyhow;
-use roxmltree::Document;
+use ego_tree::NodeRef;
+use quick_xml::events::Event;
+use quick_xml::name::QName;
+use quick_xml::Reader;
 use rtf_parser::RtfDocument;
-use scraper::{Html, Selector};
+use scraper::{Html, Node, Selector};
 use zip::ZipArchive;
 use crate::Format;
 use crate::tools::*;
 
-pub fn convert_file(path: &Path) -> anyhow::Result<String> {
+// ---------- shared XML streaming extractor ----------
+
+// Which elements a given XML dialect wants text skipped from, which are
+// "block level" (closing one ends a paragraph/row rather than just running
+// into the next span of text), and which self-closing elements stand in for
+// a single character of their own (DOCX's `<w:tab/>`/`<w:br/>`).
+#[derive(Clone, Copy, PartialEq)]
+pub enum XmlKind {
+    Docx,
+    Epub,
+    Fb2,
+}
+
+impl XmlKind {
+    fn skips(self, local_name: &str) -> bool {
+        match self {
+            XmlKind::Docx => local_name == "instrText",
+            XmlKind::Epub => matches!(local_name, "script" | "style"),
+            XmlKind::Fb2 => false,
+        }
+    }
+
+    fn is_block(self, local_name: &str) -> bool {
+        match self {
+            XmlKind::Docx => matches!(local_name, "p" | "tr"),
+            XmlKind::Epub => matches!(local_name, "p" | "div" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "li" | "tr"),
+            XmlKind::Fb2 => matches!(local_name, "p" | "title" | "subtitle" | "section" | "empty-line"),
+        }
+    }
+
+    fn empty_element_char(self, local_name: &str) -> Option<char> {
+        match self {
+            XmlKind::Docx => match local_name {
+                "tab" => Some('\t'),
+                "br" => Some('\n'),
+                _ => None,
+            },
+            XmlKind::Epub => (local_name == "br").then_some('\n'),
+            XmlKind::Fb2 => None,
+        }
+    }
+}
+
+fn local_name<'a>(name: QName<'a>) -> Cow<'a, str> {
+    String::from_utf8_lossy(name.local_name().into_inner())
+}
+
+// Appends `text` to `out`, collapsing every run of whitespace (the
+// indentation/line-wrapping inside an XML source document) down to a single
+// space, and never adding a leading space right after a block boundary.
+fn push_collapsed(out: &mut String, text: &str) {
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            if !out.is_empty() && !out.ends_with(char::is_whitespace) {
+                out.push(' ');
+            }
+        } else {
+            out.push(ch);
+        }
+    }
+}
+
+// Collapses any run of newlines (and the blank lines they frame) into a
+// single `\n`, so nested block closings (`</p></div>`) produce one paragraph
+// break rather than several.
+fn collapse_blank_lines(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for line in s.split('\n') {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+// Pull-parses `data` once, decoding it through its declared/BOM encoding
+// (`decode_xml_bytes`) and emitting text incrementally instead of building a
+// full DOM. Elements `kind` doesn't want (DOCX `w:instrText`, EPUB
+// `<script>`/`<style>`) are skipped via a small stack of open element names;
+// `<!DOCTYPE>` is consumed as a parser event rather than stripped out of the
+// source text beforehand. Block-level boundaries (`p`, `div`, `h1`-`h6`,
+// `li`, table rows, DOCX `w:p`) become a newline; everything else collapses
+// to plain inline whitespace.
+pub fn extract_xml_streaming(path: &Path, data: &[u8], kind: XmlKind, encoding_hint: Option<&str>) -> anyhow::Result<String> {
+    let decoded = decode_xml_bytes(data, encoding_hint)?;
+    warn_if_lossy(path, &decoded);
+    let text = decoded.text;
+
+    let mut reader = Reader::from_str(&text);
+    reader.config_mut().trim_text(false);
+
+    let mut stack: Vec<String> = Vec::new();
+    let mut buf = Vec::new();
+    let mut out = String::with_capacity(text.len() / 2);
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => stack.push(local_name(e.name()).into_owned()),
+            Event::End(e) => {
+                let name = local_name(e.name());
+                if !stack.iter().any(|n| kind.skips(n)) && kind.is_block(&name) {
+                    out.push('\n');
+                }
+                stack.pop();
+            }
+            Event::Empty(e) => {
+                let name = local_name(e.name());
+                if !stack.iter().any(|n| kind.skips(n)) {
+                    if let Some(ch) = kind.empty_element_char(&name) {
+                        out.push(ch);
+                    } else if kind.is_block(&name) {
+                        out.push('\n');
+                    }
+                }
+            }
+            // Text content can carry entity references (`&amp;`); CDATA
+            // content is literal by XML semantics - an `&` inside
+            // `<![CDATA[...]]>` is not an entity reference - so only
+            // `Event::Text` is routed through entity decoding.
+            Event::Text(e) if !stack.iter().any(|n| kind.skips(n)) => {
+                let text = std::str::from_utf8(e.as_ref())?;
+                push_collapsed(&mut out, &decode_html_entities(text));
+            }
+            Event::CData(e) if !stack.iter().any(|n| kind.skips(n)) => {
+                push_collapsed(&mut out, std::str::from_utf8(e.as_ref())?);
+            }
+            Event::Text(_) | Event::CData(_) => {}
+            Event::Eof => break,
+            // DOCTYPE (and everything else) is just consumed as an event -
+            // there is no string surgery to strip it from the source first.
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(collapse_blank_lines(&out))
+}
+
+pub fn convert_file(path: &Path, encoding_hint: Option<&str>) -> anyhow::Result<String> {
     let ext = path.extension()
         .and_then(|s| s.to_str())
         .unwrap_or_default()
         .to_lowercase();
 
     match ext.as_str() {
-        "epub"  => extract_zipped(path, Format::Epub),
-        "fb2"   => extract_fb2(path),
-        "docx"  => extract_zipped(path, Format::Docx),
-        "rtf"   => extract_rtf(path),
-        "html" | "htm" => extract_html(path),
-        "txt" => convert_to_utf8(path),
+        "epub"  => extract_zipped(path, Format::Epub, encoding_hint),
+        "fb2"   => extract_fb2(path, encoding_hint),
+        "docx"  => extract_zipped(path, Format::Docx, encoding_hint),
+        "rtf"   => extract_rtf(path, encoding_hint),
+        "html" | "htm" => extract_html(path, encoding_hint),
+        "eml" | "mht" | "mhtml" => extract_eml(path, encoding_hint),
+        "txt" => convert_to_utf8(path, encoding_hint),
         _ => anyhow::bail!("unsupported extension"),
     }
 }
 
 // ---------- EPUB/DOCX ----------
-pub fn extract_zipped(path: &Path, format: Format) -> anyhow::Result<String> {
+pub fn extract_zipped(path: &Path, format: Format, encoding_hint: Option<&str>) -> anyhow::Result<String> {
     let file = File::open(path)?;
     let mut archive = ZipArchive::new(file)?;
-    let mut buf = String::new();
-    for i in 0..archive.len() {
-        let mut f = archive.by_index(i)?;
-        if format == Format::Epub && (f.name().ends_with(".xhtml") || f.name().ends_with(".html"))
-                ||
-           format == Format::Docx && f.name() == "word/document.xml"
-        {
-            let mut data = String::new();
-            f.read_to_string(&mut data)?;
-
-            let cleaned_raw_xml = sanitize_xml(data.as_bytes())?;
-            let doc = Document::parse(&cleaned_raw_xml)?;
-
-            for n in doc.descendants().filter(|n| n.is_text()) {
-                if let Some(t) = n.text() && !t.is_empty() {
-                    buf.push_str(t);
-                    buf.push(' ');
-                }
+
+    match format {
+        Format::Docx => {
+            match read_zip_member_bytes(&mut archive, "word/document.xml")? {
+                Some(data) => extract_xml_streaming(path, &data, XmlKind::Docx, encoding_hint),
+                None => Ok(String::new()),
             }
         }
+        Format::Epub => extract_epub_body(path, &mut archive, encoding_hint),
     }
-    Ok(buf)
 }
 
-// ---------- FB2 ----------
-pub fn extract_fb2(path: &Path) -> anyhow::Result<String> {
-    let data = fs::read(path)?;
-    let cleaned_raw_xml = sanitize_xml(&data)?;
-    let doc = Document::parse(&cleaned_raw_xml)?;
-
+// Extracts EPUB body text in spine (reading) order: `META-INF/container.xml`
+// -> OPF `<manifest>`/`<spine>` gives the ordered list of content documents,
+// rather than concatenating whatever order the ZIP happens to store them in.
+// Falls back to every `.xhtml`/`.html` member in archive order if the EPUB
+// has no usable container/OPF.
+fn extract_epub_body(path: &Path, archive: &mut ZipArchive<File>, encoding_hint: Option<&str>) -> anyhow::Result<String> {
     let mut buf = String::new();
-    for n in doc.descendants().filter(|n| n.is_text()) {
-        if let Some(t) = n.text() && !t.is_empty() {
-            buf.push_str(t);
-            buf.push(' ');
+
+    match locate_epub_spine(path, archive)? {
+        Some(content_paths) => {
+            for content_path in content_paths {
+                if let Some(data) = read_zip_member_bytes(archive, &content_path)? {
+                    buf.push_str(&extract_xml_streaming(path, &data, XmlKind::Epub, encoding_hint)?);
+                    buf.push('\n');
+                }
+            }
+        }
+        None => {
+            for i in 0..archive.len() {
+                let mut f = archive.by_index(i)?;
+                if f.name().ends_with(".xhtml") || f.name().ends_with(".html") {
+                    let mut data = Vec::new();
+                    f.read_to_end(&mut data)?;
+                    buf.push_str(&extract_xml_streaming(path, &data, XmlKind::Epub, encoding_hint)?);
+                    buf.push('\n');
+                }
+            }
         }
     }
 
     Ok(buf)
 }
 
+// ---------- FB2 ----------
+pub fn extract_fb2(path: &Path, encoding_hint: Option<&str>) -> anyhow::Result<String> {
+    let data = fs::read(path)?;
+    extract_xml_streaming(path, &data, XmlKind::Fb2, encoding_hint)
+}
+
 // ---------- RTF ----------
-pub fn extract_rtf(path: &Path) -> anyhow::Result<String> {
+pub fn extract_rtf(path: &Path, encoding_hint: Option<&str>) -> anyhow::Result<String> {
     let data = fs::read(path)?;
-    let raw_rtf = safe_decode_bytes(&data)?;
-    let cleaned_raw_rtf = raw_rtf.trim();
+    let raw_rtf = safe_decode_bytes(&data, encoding_hint);
+    warn_if_lossy(path, &raw_rtf);
+    let cleaned_raw_rtf = raw_rtf.text.trim();
 
     // Decode RTF escape sequences like \'xx into actual bytes
     let decoded_rtf = decode_rtf_escapes(cleaned_raw_rtf)?;
@@ -89,9 +249,13 @@ pub fn extract_rtf(path: &Path) -> anyhow::Result<String> {
 }
 
 // ---------- HTML | HTM ----------
-pub fn extract_html(path: &Path) -> anyhow::Result<String> {
+pub fn extract_html(path: &Path, encoding_hint: Option<&str>) -> anyhow::Result<String> {
     let data = fs::read(path)?;
-    let cleaned_raw_xml = sanitize_xml(&data)?;
+    extract_html_bytes(&data, encoding_hint)
+}
+
+fn extract_html_bytes(data: &[u8], encoding_hint: Option<&str>) -> anyhow::Result<String> {
+    let cleaned_raw_xml = sanitize_xml(data, encoding_hint)?;
     let doc = Html::parse_document(&cleaned_raw_xml);
 
     let selector = Selector::parse("body")
@@ -99,18 +263,533 @@ pub fn extract_html(path: &Path) -> anyhow::Result<String> {
 
     let mut buf = String::new();
     for el in doc.select(&selector) {
-        buf.push_str(&el.text().collect::<Vec<_>>().join(" "));
+        walk_html_text(*el, &mut buf);
     }
 
-    Ok(buf)
+    Ok(decode_html_entities(&collapse_blank_lines(&buf)))
+}
+
+// Walks an HTML node tree the same way `extract_xml_streaming` walks EPUB
+// XHTML: `<script>`/`<style>` content is skipped, inline whitespace
+// collapses to a single space, and closing a block-level element (`p`,
+// `div`, `h1`-`h6`, `li`, table rows) or hitting a `<br>` starts a new line.
+fn walk_html_text(node: NodeRef<Node>, out: &mut String) {
+    match node.value() {
+        Node::Text(text) => push_collapsed(out, text),
+        Node::Element(el) => {
+            let name = el.name();
+            if XmlKind::Epub.skips(name) {
+                return;
+            }
+            if let Some(ch) = XmlKind::Epub.empty_element_char(name) {
+                out.push(ch);
+            }
+            for child in node.children() {
+                walk_html_text(child, out);
+            }
+            if XmlKind::Epub.is_block(name) {
+                out.push('\n');
+            }
+        }
+        _ => {}
+    }
+}
+
+// ---------- EML / MHT(ML) ----------
+pub fn extract_eml(path: &Path, encoding_hint: Option<&str>) -> anyhow::Result<String> {
+    let data = fs::read(path)?;
+    // The body is kept as raw bytes - a multipart message's parts can each
+    // declare their own charset, so only the (always-ASCII) header block is
+    // decoded here; `decode_mime_body` decodes each leaf part's own bytes
+    // exactly once, against its own declared charset.
+    let (header_bytes, body) = split_headers_and_body_bytes(&data);
+    let raw_headers = safe_decode_bytes(header_bytes, encoding_hint);
+    warn_if_lossy(path, &raw_headers);
+
+    let headers = unfold_header_lines(&raw_headers.text);
+
+    let mut out = String::new();
+    if let Some(subject) = get_header(&headers, "Subject") {
+        out.push_str("Subject: ");
+        out.push_str(&decode_encoded_words(&subject));
+        out.push('\n');
+    }
+    if let Some(from) = get_header(&headers, "From") {
+        out.push_str("From: ");
+        out.push_str(&decode_encoded_words(&from));
+        out.push('\n');
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+
+    out.push_str(&extract_mime_part(&headers, body)?);
+    Ok(out)
+}
+
+// Extracts the readable text of one MIME part (or of a whole message, since
+// a message's top-level headers/body are just its outermost part), walking
+// into nested multipart bodies and preferring `text/plain` over `text/html`.
+fn extract_mime_part(headers: &str, body: &[u8]) -> anyhow::Result<String> {
+    let content_type = get_header(headers, "Content-Type").unwrap_or_else(|| "text/plain".to_string());
+    let (mime_type, params) = parse_header_params(&content_type);
+
+    if let Some(boundary) = params.get("boundary") {
+        return extract_mime_multipart(body, boundary);
+    }
+
+    let transfer_encoding = get_header(headers, "Content-Transfer-Encoding").unwrap_or_default();
+    let charset = params.get("charset").cloned().unwrap_or_else(|| "utf-8".to_string());
+    let decoded = decode_mime_body(body, &transfer_encoding, &charset)?;
+
+    if mime_type.eq_ignore_ascii_case("text/html") {
+        extract_html_bytes(decoded.as_bytes(), None)
+    } else {
+        Ok(decoded)
+    }
+}
+
+// Splits a multipart body on `--boundary` (and its closing `--boundary--`),
+// preferring the first `text/plain` part and falling back to `text/html`.
+// Splitting happens on raw bytes rather than an already-decoded string, since
+// a multipart message's parts can each declare a different charset - decoding
+// the whole body up front before decoding again against a part's own charset
+// would double-decode it.
+fn extract_mime_multipart(body: &[u8], boundary: &str) -> anyhow::Result<String> {
+    let delimiter = format!("--{boundary}");
+
+    let mut plain: Option<String> = None;
+    let mut html: Option<String> = None;
+
+    for chunk in split_on_bytes(body, delimiter.as_bytes()).into_iter().skip(1) {
+        let chunk = chunk.strip_prefix(b"\r\n").unwrap_or(chunk);
+        let chunk = chunk.strip_prefix(b"\n").unwrap_or(chunk);
+        if chunk.starts_with(b"--") || chunk.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        let (part_header_bytes, part_body) = split_headers_and_body_bytes(chunk);
+        let part_headers = unfold_header_lines(&safe_decode_bytes(part_header_bytes, None).text);
+        let content_type = get_header(&part_headers, "Content-Type").unwrap_or_else(|| "text/plain".to_string());
+        let (mime_type, params) = parse_header_params(&content_type);
+
+        if let Some(nested_boundary) = params.get("boundary") {
+            if let Ok(text) = extract_mime_multipart(part_body, nested_boundary)
+                && !text.trim().is_empty()
+            {
+                return Ok(text);
+            }
+            continue;
+        }
+
+        let transfer_encoding = get_header(&part_headers, "Content-Transfer-Encoding").unwrap_or_default();
+        let charset = params.get("charset").cloned().unwrap_or_else(|| "utf-8".to_string());
+
+        if plain.is_none() && mime_type.eq_ignore_ascii_case("text/plain") {
+            plain = decode_mime_body(part_body, &transfer_encoding, &charset).ok();
+        } else if html.is_none() && mime_type.eq_ignore_ascii_case("text/html") {
+            html = decode_mime_body(part_body, &transfer_encoding, &charset).ok();
+        }
+    }
+
+    if let Some(text) = plain {
+        return Ok(text);
+    }
+    if let Some(html) = html {
+        return extract_html_bytes(html.as_bytes(), None);
+    }
+    Ok(String::new())
+}
+
+// Quoted-printable and base64 transfer encodings are always pure ASCII by
+// definition - it's only the "8bit"/"binary"/unspecified case that carries the
+// part's declared charset directly - so `body` is decoded against `charset`
+// exactly once, no matter which transfer encoding produced it.
+fn decode_mime_body(body: &[u8], transfer_encoding: &str, charset: &str) -> anyhow::Result<String> {
+    let bytes = match transfer_encoding.to_ascii_lowercase().as_str() {
+        "quoted-printable" => decode_quoted_printable(std::str::from_utf8(body)?),
+        "base64" => decode_base64(std::str::from_utf8(body)?)?,
+        _ => body.to_vec(),
+    };
+    decode_with_label(&bytes, charset)
 }
 
 // ---------- TXT ----------
-pub fn convert_to_utf8(path: &Path) -> anyhow::Result<String> {
+pub fn convert_to_utf8(path: &Path, encoding_hint: Option<&str>) -> anyhow::Result<String> {
     let data = fs::read(path)?;
-    let txt =
-        if is_utf8(&data) { String::from_utf8_lossy(&data) }
-        else { decode_bytes(&data)? };
+    let txt = if is_utf8(&data) {
+        String::from_utf8_lossy(&data)
+    } else {
+        let decoded = decode_bytes(&data, encoding_hint);
+        warn_if_lossy(path, &decoded);
+        decoded.text
+    };
 
     Ok(txt.trim().to_string())
 }
+
+// Reports to stderr when the decode that was used had to substitute
+// replacement characters (a forced `-e` encoding that doesn't fit the bytes,
+// or no candidate - declared or detected - decoding cleanly), so the garbled
+// characters that can follow don't pass silently.
+fn warn_if_lossy(path: &Path, decoded: &DecodedText<'_>) {
+    if decoded.lossy {
+        eprintln!("?? {} - decoded as {} with replacement characters", path.display(), decoded.encoding.name());
+    }
+}
+
+// ---------- Metadata ----------
+
+// Bibliographic metadata pulled from a container's own descriptive markup,
+// as opposed to its body text.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub language: Option<String>,
+    pub date: Option<String>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.authors.is_empty() && self.language.is_none() && self.date.is_none()
+    }
+
+    // Renders as the small header block `main_logic` writes to the
+    // `.meta.txt` sidecar next to the converted text.
+    pub fn to_header_block(&self) -> String {
+        let mut out = String::new();
+        if let Some(title) = &self.title {
+            out.push_str(&format!("Title: {title}\n"));
+        }
+        if !self.authors.is_empty() {
+            out.push_str(&format!("Author: {}\n", self.authors.join(", ")));
+        }
+        if let Some(language) = &self.language {
+            out.push_str(&format!("Language: {language}\n"));
+        }
+        if let Some(date) = &self.date {
+            out.push_str(&format!("Date: {date}\n"));
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+}
+
+pub fn extract_metadata(path: &Path) -> anyhow::Result<Metadata> {
+    let ext = path.extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    match ext.as_str() {
+        "epub" => extract_epub_metadata(path),
+        "fb2" => extract_fb2_metadata(path),
+        "docx" => extract_docx_metadata(path),
+        "html" | "htm" => extract_html_metadata(path),
+        _ => Ok(Metadata::default()),
+    }
+}
+
+// Reads one named member out of a ZIP archive, returning `None` if the
+// archive has no such member.
+fn read_zip_member_bytes(archive: &mut ZipArchive<File>, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    for i in 0..archive.len() {
+        let mut f = archive.by_index(i)?;
+        if f.name() == name {
+            let mut data = Vec::new();
+            f.read_to_end(&mut data)?;
+            return Ok(Some(data));
+        }
+    }
+    Ok(None)
+}
+
+// Same as `read_zip_member_bytes`, decoded through `decode_xml_bytes`.
+fn read_zip_member_as_string(path: &Path, archive: &mut ZipArchive<File>, name: &str) -> anyhow::Result<Option<String>> {
+    match read_zip_member_bytes(archive, name)? {
+        Some(data) => {
+            let decoded = decode_xml_bytes(&data, None)?;
+            warn_if_lossy(path, &decoded);
+            Ok(Some(decoded.text.into_owned()))
+        }
+        None => Ok(None),
+    }
+}
+
+// Reads the `full-path` of the first `<rootfile>` out of an EPUB/OPF
+// `META-INF/container.xml`, i.e. the path to the package (OPF) document.
+fn find_opf_path(container_xml: &str) -> Option<String> {
+    let mut reader = Reader::from_str(container_xml);
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf).ok()? {
+            Event::Start(e) | Event::Empty(e) if local_name(e.name()) == "rootfile" => {
+                for attr in e.attributes().flatten() {
+                    if local_name(attr.key) == "full-path" {
+                        return Some(String::from_utf8_lossy(&attr.value).into_owned());
+                    }
+                }
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+// Scans `xml` for the given top-level element local names and collects the
+// text of each occurrence - enough for the flat `dc:title`/`dc:creator`
+// style fields in an OPF or `docProps/core.xml`.
+fn extract_opf_fields(xml: &str, fields: &[&str]) -> HashMap<String, Vec<String>> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut out: HashMap<String, Vec<String>> = HashMap::new();
+    let mut current: Option<String> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let name = local_name(e.name()).into_owned();
+                current = fields.contains(&name.as_str()).then_some(name);
+            }
+            Ok(Event::Text(e)) => {
+                if let Some(field) = &current
+                    && let Ok(text) = std::str::from_utf8(e.as_ref())
+                    && !text.trim().is_empty()
+                {
+                    out.entry(field.clone()).or_default().push(text.trim().to_string());
+                }
+            }
+            Ok(Event::End(_)) => current = None,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    out
+}
+
+// Resolves `META-INF/container.xml` -> OPF `<rootfile full-path="...">` and
+// reads that package document, returning its containing directory (for
+// resolving relative `href`s) alongside its decoded text. `None` means the
+// EPUB has no usable container/OPF.
+fn locate_epub_opf(path: &Path, archive: &mut ZipArchive<File>) -> anyhow::Result<Option<(String, String)>> {
+    let Some(container_xml) = read_zip_member_as_string(path, archive, "META-INF/container.xml")? else {
+        return Ok(None);
+    };
+    let Some(opf_path) = find_opf_path(&container_xml) else {
+        return Ok(None);
+    };
+    let Some(opf_xml) = read_zip_member_as_string(path, archive, &opf_path)? else {
+        return Ok(None);
+    };
+
+    let opf_dir = opf_path.rsplit_once('/').map(|(dir, _)| dir.to_string()).unwrap_or_default();
+    Ok(Some((opf_dir, opf_xml)))
+}
+
+// Resolves the OPF's `<manifest>`/`<spine>` into the ordered list of content
+// document paths (relative to the ZIP root). `None` means no usable
+// container/OPF/spine was found.
+fn locate_epub_spine(path: &Path, archive: &mut ZipArchive<File>) -> anyhow::Result<Option<Vec<String>>> {
+    let Some((opf_dir, opf_xml)) = locate_epub_opf(path, archive)? else {
+        return Ok(None);
+    };
+    let content_paths = parse_spine(&opf_xml, &opf_dir);
+    Ok((!content_paths.is_empty()).then_some(content_paths))
+}
+
+// Parses an OPF package document's `<manifest>` (item id -> href) and
+// `<spine>` (ordered itemref idrefs) into the ordered list of content
+// document paths, each resolved against `opf_dir`.
+fn parse_spine(opf_xml: &str, opf_dir: &str) -> Vec<String> {
+    let mut reader = Reader::from_str(opf_xml);
+    let mut buf = Vec::new();
+
+    let mut manifest: HashMap<String, String> = HashMap::new();
+    let mut spine_idrefs: Vec<String> = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let name = local_name(e.name());
+                if name == "item" {
+                    let mut id = None;
+                    let mut href = None;
+                    for attr in e.attributes().flatten() {
+                        match local_name(attr.key).as_ref() {
+                            "id" => id = Some(String::from_utf8_lossy(&attr.value).into_owned()),
+                            "href" => href = Some(String::from_utf8_lossy(&attr.value).into_owned()),
+                            _ => {}
+                        }
+                    }
+                    if let (Some(id), Some(href)) = (id, href) {
+                        manifest.insert(id, href);
+                    }
+                } else if name == "itemref" {
+                    for attr in e.attributes().flatten() {
+                        if local_name(attr.key) == "idref" {
+                            spine_idrefs.push(String::from_utf8_lossy(&attr.value).into_owned());
+                        }
+                    }
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    spine_idrefs.iter()
+        .filter_map(|idref| manifest.get(idref))
+        .map(|href| join_zip_path(opf_dir, &percent_decode(href)))
+        .collect()
+}
+
+// Joins a ZIP-internal directory with a relative href, resolving `.`/`..`
+// segments the way a filesystem path join would.
+fn join_zip_path(dir: &str, href: &str) -> String {
+    let mut parts: Vec<&str> = dir.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => { parts.pop(); }
+            _ => parts.push(segment),
+        }
+    }
+    parts.join("/")
+}
+
+fn extract_epub_metadata(path: &Path) -> anyhow::Result<Metadata> {
+    let file = File::open(path)?;
+    let mut archive = ZipArchive::new(file)?;
+
+    let Some((_, opf_xml)) = locate_epub_opf(path, &mut archive)? else {
+        return Ok(Metadata::default());
+    };
+
+    let mut fields = extract_opf_fields(&opf_xml, &["title", "creator", "language"]);
+    Ok(Metadata {
+        title: fields.get_mut("title").map(|v| v.remove(0)),
+        authors: fields.remove("creator").unwrap_or_default(),
+        language: fields.get_mut("language").map(|v| v.remove(0)),
+        date: None,
+    })
+}
+
+fn extract_docx_metadata(path: &Path) -> anyhow::Result<Metadata> {
+    let file = File::open(path)?;
+    let mut archive = ZipArchive::new(file)?;
+
+    let Some(core_xml) = read_zip_member_as_string(path, &mut archive, "docProps/core.xml")? else {
+        return Ok(Metadata::default());
+    };
+
+    let mut fields = extract_opf_fields(&core_xml, &["title", "creator", "created"]);
+    Ok(Metadata {
+        title: fields.get_mut("title").map(|v| v.remove(0)),
+        authors: fields.remove("creator").unwrap_or_default(),
+        language: None,
+        date: fields.get_mut("created").map(|v| v.remove(0)),
+    })
+}
+
+fn extract_fb2_metadata(path: &Path) -> anyhow::Result<Metadata> {
+    let data = fs::read(path)?;
+    let decoded = decode_xml_bytes(&data, None)?;
+    warn_if_lossy(path, &decoded);
+    let xml = decoded.text;
+
+    let mut reader = Reader::from_str(&xml);
+    let mut buf = Vec::new();
+    let mut stack: Vec<String> = Vec::new();
+
+    let mut in_title_info = false;
+    let mut in_author = false;
+    let mut current_author = String::new();
+
+    let mut title = None;
+    let mut authors = Vec::new();
+    let mut language = None;
+    let mut date = None;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => {
+                let name = local_name(e.name()).into_owned();
+                if name == "title-info" {
+                    in_title_info = true;
+                } else if in_title_info && name == "author" {
+                    in_author = true;
+                    current_author.clear();
+                }
+                stack.push(name);
+            }
+            Event::Text(e) if in_title_info => {
+                let text = std::str::from_utf8(e.as_ref())?.trim();
+                if !text.is_empty() {
+                    match stack.last().map(String::as_str) {
+                        Some("book-title") => title = Some(text.to_string()),
+                        Some("lang") => language = Some(text.to_string()),
+                        Some("date") => date = Some(text.to_string()),
+                        Some("first-name" | "last-name" | "middle-name") if in_author => {
+                            if !current_author.is_empty() {
+                                current_author.push(' ');
+                            }
+                            current_author.push_str(text);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(e) => {
+                let name = local_name(e.name());
+                if name == "author" && in_author {
+                    in_author = false;
+                    if !current_author.is_empty() {
+                        authors.push(current_author.clone());
+                    }
+                } else if name == "title-info" {
+                    in_title_info = false;
+                }
+                stack.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(Metadata { title, authors, language, date })
+}
+
+fn extract_html_metadata(path: &Path) -> anyhow::Result<Metadata> {
+    let data = fs::read(path)?;
+    let cleaned_raw_xml = sanitize_xml(&data, None)?;
+    let doc = Html::parse_document(&cleaned_raw_xml);
+
+    let title_selector = Selector::parse("title").map_err(|e| anyhow!(e.to_string()))?;
+    let title = doc.select(&title_selector)
+        .next()
+        .map(|el| el.text().collect::<String>().trim().to_string())
+        .filter(|t| !t.is_empty());
+
+    let author_selector = Selector::parse(r#"meta[name="author" i]"#).map_err(|e| anyhow!(e.to_string()))?;
+    let authors = doc.select(&author_selector)
+        .filter_map(|el| el.value().attr("content"))
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let html_selector = Selector::parse("html").map_err(|e| anyhow!(e.to_string()))?;
+    let language = doc.select(&html_selector)
+        .next()
+        .and_then(|el| el.value().attr("lang"))
+        .map(|s| s.to_string());
+
+    Ok(Metadata { title, authors, language, date: None })
+}
@@ -1,12 +1,23 @@
 use std::fs;
 use std::path::PathBuf;
 use walkdir::WalkDir;
-use crate::core::convert_file;
+use crate::core::{convert_file, extract_metadata};
 
 pub fn main_logic(dir: &PathBuf, args: &[String]) -> anyhow::Result<()> {
-    let sup_ext = ["epub", "fb2", "docx", "rtf", "html", "htm", "txt"];
+    let sup_ext = ["epub", "fb2", "docx", "rtf", "html", "htm", "eml", "mht", "mhtml", "txt"];
     let thrash_ext = ["djvu", "djv", "doc", "chm", "xls", "jpg", "jpeg", "gif", "png", "zip", "rar", "diz"];
 
+    // Parsed uniformly by name rather than by a fixed arg index, so any
+    // combination of flags (e.g. `-e windows-1251 -m`) works regardless of
+    // where `-e`'s value shifts the rest of the argument list.
+    let remove_originals = args.iter().any(|a| a == "-r");
+    let remove_thrash = args.iter().any(|a| a == "-rt");
+    let write_metadata = args.iter().any(|a| a == "-m");
+    let forced_encoding = args.iter()
+        .position(|a| a == "-e")
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str);
+
     for entry in WalkDir::new(dir).into_iter().filter_map(anyhow::Result::ok) {
         if entry.file_type().is_file() &&
             let Some(ext) = entry.path().extension()
@@ -17,18 +28,26 @@ pub fn main_logic(dir: &PathBuf, args: &[String]) -> anyhow::Result<()> {
             if sup_ext.contains(&ext) {
                 let out = entry.path().with_extension("txt");
 
-                match convert_file(entry.path()) {
+                match convert_file(entry.path(), forced_encoding) {
                     Ok(text) => {
                         let text = text.trim();
 
                         if !text.is_empty() {
                             println!("-> {}", out.display());
 
-                            fs::write(out, text)?;
+                            fs::write(&out, text)?;
+
+                            if write_metadata {
+                                match extract_metadata(entry.path()) {
+                                    Ok(meta) if !meta.is_empty() => {
+                                        fs::write(entry.path().with_extension("meta.txt"), meta.to_header_block())?;
+                                    }
+                                    Ok(_) => {}
+                                    Err(e) => println!("xxx {} (metadata) - {e}", out.display()),
+                                }
+                            }
 
-                            if (args.get(2) == Some(&"-r".to_owned()) || args.get(3) == Some(&"-r".to_owned())) &&
-                                !ext.eq_ignore_ascii_case("txt")
-                            {
+                            if remove_originals && !ext.eq_ignore_ascii_case("txt") {
                                 fs::remove_file(entry.path())?
                             }
 
@@ -39,9 +58,7 @@ pub fn main_logic(dir: &PathBuf, args: &[String]) -> anyhow::Result<()> {
                     }
                 }
             }
-            else if thrash_ext.contains(&ext) &&
-                (args.get(2) == Some(&"-rt".to_owned()) || args.get(3) == Some(&"-rt".to_owned()))
-            {
+            else if thrash_ext.contains(&ext) && remove_thrash {
                 fs::remove_file(entry.path())?
             }
         }
@@ -1,8 +1,11 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 use anyhow::anyhow;
+use base64::Engine;
 use chardetng::EncodingDetector;
-use encoding_rs::UTF_8;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1251, WINDOWS_1252};
 use walkdir::WalkDir;
 
 pub fn is_utf8(data: &[u8]) -> bool {
@@ -10,56 +13,81 @@ pub fn is_utf8(data: &[u8]) -> bool {
     let (_, _, utf8_errors) = UTF_8.decode(data);
     !utf8_errors
 }
-pub fn decode_bytes(data: &'_ [u8]) -> anyhow::Result<Cow<'_, str>> {
 
-    // Otherwise, detect encoding
+// The result of decoding a byte slice whose encoding wasn't already known
+// for certain: which encoding was actually used, and whether any bytes had
+// to be replaced with U+FFFD to get there.
+pub struct DecodedText<'a> {
+    pub text: Cow<'a, str>,
+    pub encoding: &'static Encoding,
+    pub lossy: bool,
+}
+
+// A caller-supplied `-e <label>` hint is a forced override, not just another
+// guess to rank by error count: single-byte encodings (windows-1252,
+// windows-1251, koi8-r, ...) decode *every* byte sequence without error, so
+// ranking the hint alongside chardetng's guess by `had_errors` alone can
+// never actually pick the hint over a wrong single-byte guess. If `hint`
+// names a real encoding, use it unconditionally.
+//
+// Without a hint, tries chardetng's own best guess, then common legacy
+// single-byte fallbacks (windows-1252, windows-1251 - encoding_rs resolves
+// the `iso-8859-1` label to windows-1252 per the WHATWG Encoding standard,
+// so that's covered by the first) - and returns the first that decodes
+// `data` with zero errors. If none are clean, falls back to a lossy decode
+// of chardetng's guess instead of failing the whole conversion over one
+// stray byte.
+pub fn decode_bytes<'a>(data: &'a [u8], hint: Option<&str>) -> DecodedText<'a> {
+    if let Some(hinted) = hint.and_then(|h| Encoding::for_label(h.trim().as_bytes())) {
+        let (text, _, had_errors) = hinted.decode(data);
+        return DecodedText { text, encoding: hinted, lossy: had_errors };
+    }
+
     let mut detector = EncodingDetector::new();
     detector.feed(data, true);
-    let enc = detector.guess(None, false);
+    let guessed = detector.guess(None, false);
 
-    // Try decode text
-    let (text, _, had_errors) = enc.decode(data);
-    if had_errors {
-        let enc_name = enc.name();
-        return Err(anyhow!("decode errors with {}", enc_name))
+    let mut tried: Vec<&'static str> = Vec::new();
+    for encoding in [guessed, WINDOWS_1252, WINDOWS_1251] {
+        if tried.contains(&encoding.name()) {
+            continue;
+        }
+        tried.push(encoding.name());
+
+        let (text, _, had_errors) = encoding.decode(data);
+        if !had_errors {
+            return DecodedText { text, encoding, lossy: false };
+        }
     }
-    Ok(text)
+
+    let (text, _, _) = guessed.decode(data);
+    DecodedText { text, encoding: guessed, lossy: true }
 }
-pub fn safe_decode_bytes(data: &'_ [u8]) -> anyhow::Result<Cow<'_, str>> {
-    if data.is_empty() {
-        return Ok(Cow::Borrowed(""));
-    }
-
-    let doc =
-        if data.len() >= 2 {
-            match data {
-                [0xFF, 0xFE, ..] => {
-                    // UTF-16 LE
-                    let u16s: Vec<u16> = data[2..]
-                        .chunks_exact(2)
-                        .map(|b| u16::from_le_bytes([b[0], b[1]]))
-                        .collect();
-                    Cow::Owned(String::from_utf16_lossy(&u16s))
-                }
-                [0xFE, 0xFF, ..] => {
-                    // UTF-16 BE
-                    let u16s: Vec<u16> = data[2..]
-                        .chunks_exact(2)
-                        .map(|b| u16::from_be_bytes([b[0], b[1]]))
-                        .collect();
-                    Cow::Owned(String::from_utf16_lossy(&u16s))
-                }
-                _ => {
-                    if is_utf8(data) { String::from_utf8_lossy(data) }
-                    // Otherwise, detect encoding with chardetng
-                    else { decode_bytes(data)? }
-                }
-            }
-        }
-        else { return Ok(Cow::Borrowed("")) };
 
-    Ok(doc)
+// Like `decode_bytes`, but first handles the cases that don't need
+// detection at all: a leading UTF-16 BOM, or text that's already valid
+// UTF-8. `hint` is only consulted when detection is actually needed.
+pub fn safe_decode_bytes<'a>(data: &'a [u8], hint: Option<&str>) -> DecodedText<'a> {
+    if data.len() < 2 {
+        return DecodedText { text: Cow::Borrowed(""), encoding: UTF_8, lossy: false };
+    }
+
+    match data {
+        [0xFF, 0xFE, ..] => {
+            let u16s: Vec<u16> = data[2..].chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect();
+            let lossy = char::decode_utf16(u16s.iter().copied()).any(|r| r.is_err());
+            DecodedText { text: Cow::Owned(String::from_utf16_lossy(&u16s)), encoding: UTF_16LE, lossy }
+        }
+        [0xFE, 0xFF, ..] => {
+            let u16s: Vec<u16> = data[2..].chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect();
+            let lossy = char::decode_utf16(u16s.iter().copied()).any(|r| r.is_err());
+            DecodedText { text: Cow::Owned(String::from_utf16_lossy(&u16s)), encoding: UTF_16BE, lossy }
+        }
+        _ if is_utf8(data) => DecodedText { text: String::from_utf8_lossy(data), encoding: UTF_8, lossy: false },
+        _ => decode_bytes(data, hint),
+    }
 }
+
 // Converts all RTF \'xx escape sequences to real characters
 pub fn decode_rtf_escapes(rtf: &str) -> anyhow::Result<String> {
     let mut bytes = Vec::with_capacity(rtf.len());
@@ -95,9 +123,257 @@ pub fn decode_rtf_escapes(rtf: &str) -> anyhow::Result<String> {
     }
 
     // Convert all collected bytes into UTF-8 string safely
-    let text = decode_bytes(&bytes)?;
+    Ok(decode_bytes(&bytes, None).text.into_owned())
+}
+// Resolves the encoding declared by a BOM, or failing that the `encoding="..."`
+// attribute of an `<?xml ... ?>` declaration, and decodes with it. If neither
+// signal is present or the one that is present doesn't actually decode
+// cleanly, falls back to the same ranked hint/guess/legacy chain
+// `decode_bytes` uses elsewhere, rather than hard-failing the whole document
+// over one untrustworthy signal. Returns `DecodedText` (rather than a bare
+// `String`) so callers can surface a lossy fallback decode to the user the
+// same way they already do for RTF/EML/TXT.
+pub fn decode_xml_bytes(data: &[u8], hint: Option<&str>) -> anyhow::Result<DecodedText<'static>> {
+    if data.is_empty() {
+        return Ok(DecodedText { text: Cow::Borrowed(""), encoding: UTF_8, lossy: false });
+    }
+
+    if let Some((encoding, bom_len)) = Encoding::for_bom(data) {
+        let (text, _, had_errors) = encoding.decode(&data[bom_len..]);
+        if !had_errors {
+            return Ok(DecodedText { text: Cow::Owned(text.into_owned()), encoding, lossy: false });
+        }
+    }
+
+    if let Some(encoding) = sniff_xml_declared_encoding(data).and_then(|label| Encoding::for_label(label.as_bytes())) {
+        let (text, _, had_errors) = encoding.decode(data);
+        if !had_errors {
+            return Ok(DecodedText { text: Cow::Owned(text.into_owned()), encoding, lossy: false });
+        }
+    }
+
+    let decoded = decode_bytes(data, hint);
+    Ok(DecodedText { text: Cow::Owned(decoded.text.into_owned()), encoding: decoded.encoding, lossy: decoded.lossy })
+}
+
+// Reads the `encoding="..."` pseudo-attribute out of a leading `<?xml ... ?>`
+// declaration. The declaration itself is always pure ASCII per the XML spec,
+// so it's safe to read before we know the document's real encoding.
+fn sniff_xml_declared_encoding(data: &[u8]) -> Option<String> {
+    let head = &data[..data.len().min(256)];
+
+    // Search for the declaration's delimiters as raw bytes rather than
+    // UTF-8-validating the whole window first: a non-UTF-8 document can
+    // have non-ASCII bytes anywhere in this window (e.g. Cyrillic body
+    // text right after the header), which would otherwise make an
+    // entirely well-formed declaration invisible to us.
+    let decl_start = find_bytes(head, b"<?xml")?;
+    let decl_end = find_bytes(&head[decl_start..], b"?>")? + decl_start;
+    let decl = std::str::from_utf8(&head[decl_start..decl_end]).ok()?;
+
+    let key = decl.find("encoding")? + "encoding".len();
+    let after_key = decl[key..].trim_start();
+    let after_eq = after_key.strip_prefix('=')?.trim_start();
+    let quote = after_eq.chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let rest = &after_eq[quote.len_utf8()..];
+    let end = rest.find(quote)?;
+    Some(rest[..end].to_string())
+}
+
+pub(crate) fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+// Splits `haystack` on every occurrence of `needle`, the same way `str::split`
+// would - used for MIME boundary splitting, where the body is still raw,
+// charset-undecoded bytes and a literal ASCII delimiter has to be found
+// without assuming the body is valid UTF-8.
+pub(crate) fn split_on_bytes<'a>(haystack: &'a [u8], needle: &[u8]) -> Vec<&'a [u8]> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while let Some(rel) = find_bytes(&haystack[start..], needle) {
+        let pos = start + rel;
+        parts.push(&haystack[start..pos]);
+        start = pos + needle.len();
+    }
+    parts.push(&haystack[start..]);
+    parts
+}
+// Decodes `data` with an explicit charset label (as found in a Content-Type
+// or encoded-word header), falling back to UTF-8 for an unrecognized label.
+pub fn decode_with_label(data: &[u8], label: &str) -> anyhow::Result<String> {
+    let encoding = Encoding::for_label(label.trim().as_bytes()).unwrap_or(UTF_8);
+    let (text, _, had_errors) = encoding.decode(data);
+    if had_errors {
+        return Err(anyhow!("invalid {} byte sequence", encoding.name()));
+    }
     Ok(text.into_owned())
 }
+
+// ---------- EPUB ----------
+
+// Decodes `%XX` percent-escapes in an OPF manifest `href` (RFC 3986). These
+// are required for any reserved character (most commonly spaces) in a ZIP
+// entry name, so the href has to be decoded before it's used to look up the
+// actual archive entry.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = bytes.get(i + 1..i + 3)
+            && let Ok(hex) = std::str::from_utf8(hex)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// ---------- EML / MIME ----------
+
+// Joins RFC 5322 folded header lines (continuation lines starting with
+// whitespace) back onto the header they continue.
+pub fn unfold_header_lines(headers: &str) -> String {
+    let normalized = headers.replace("\r\n", "\n");
+    let mut out = String::with_capacity(normalized.len());
+    for line in normalized.split('\n') {
+        if (line.starts_with(' ') || line.starts_with('\t')) && !out.is_empty() {
+            out.push(' ');
+            out.push_str(line.trim_start());
+        } else {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+// Splits a raw MIME message (or MIME part) into its header block and body at
+// the first blank line. Operates on raw bytes rather than an already-decoded
+// string, since a multipart message's parts can each declare a different
+// charset - decoding the whole message up front before decoding a part's body
+// again against its own charset would double-decode it. The header block
+// itself is always plain ASCII, so only locating the boundary needs to happen
+// before any charset is known.
+pub fn split_headers_and_body_bytes(raw: &[u8]) -> (&[u8], &[u8]) {
+    match find_bytes(raw, b"\r\n\r\n").map(|i| (i, 4)).or_else(|| find_bytes(raw, b"\n\n").map(|i| (i, 2))) {
+        Some((i, sep_len)) => (&raw[..i], &raw[i + sep_len..]),
+        None => (raw, &[]),
+    }
+}
+
+// Looks up a header by name (case-insensitive) in an already-unfolded header
+// block.
+pub fn get_header(unfolded_headers: &str, name: &str) -> Option<String> {
+    unfolded_headers.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+    })
+}
+
+// Splits a header value like `multipart/mixed; boundary="xyz"; charset=utf-8`
+// into its bare value and a lowercase-keyed parameter map.
+pub fn parse_header_params(value: &str) -> (String, HashMap<String, String>) {
+    let mut fields = value.split(';');
+    let head = fields.next().unwrap_or_default().trim().to_string();
+
+    let mut params = HashMap::new();
+    for field in fields {
+        if let Some((k, v)) = field.split_once('=') {
+            params.insert(k.trim().to_ascii_lowercase(), v.trim().trim_matches('"').to_string());
+        }
+    }
+    (head, params)
+}
+
+// Decodes quoted-printable: `=XX` hex escapes become the raw byte, and a
+// trailing `=` (soft line break) is dropped along with its line ending.
+pub fn decode_quoted_printable(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'=' if bytes[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if bytes.get(i + 1) == Some(&b'\n') => i += 2,
+            b'=' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(byte) => { out.push(byte); i += 3; }
+                    None => { out.push(b'='); i += 1; }
+                }
+            }
+            b => { out.push(b); i += 1; }
+        }
+    }
+    out
+}
+
+// Decodes a base64 body, ignoring embedded whitespace/newlines.
+pub fn decode_base64(s: &str) -> anyhow::Result<Vec<u8>> {
+    let cleaned: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    base64::engine::general_purpose::STANDARD.decode(cleaned.as_bytes())
+        .map_err(|e| anyhow!(e.to_string()))
+}
+
+// Decodes RFC 2047 encoded-words (`=?charset?B?...?=` / `=?charset?Q?...?=`)
+// found in header values such as Subject/From, leaving surrounding plain
+// text untouched.
+pub fn decode_encoded_words(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find("=?") {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start + 2..];
+
+        // The payload starts after the second `?` (charset?encoding?); a Q
+        // payload can itself begin with `=XX`, so `?=` is searched from there.
+        let payload_start = tail.match_indices('?').nth(1).map(|(i, _)| i + 1);
+        let Some(word_end) = payload_start.and_then(|p| tail[p..].find("?=").map(|e| p + e)) else {
+            out.push_str("=?");
+            rest = tail;
+            continue;
+        };
+        let word = &tail[..word_end];
+        let after = &tail[word_end + 2..];
+
+        let mut fields = word.splitn(3, '?');
+        let (Some(charset), Some(encoding), Some(payload)) = (fields.next(), fields.next(), fields.next()) else {
+            out.push_str("=?");
+            rest = tail;
+            continue;
+        };
+
+        let decoded_bytes = match encoding.to_ascii_uppercase().as_str() {
+            "B" => decode_base64(payload).unwrap_or_default(),
+            "Q" => decode_quoted_printable(&payload.replace('_', " ")),
+            _ => payload.as_bytes().to_vec(),
+        };
+
+        match decode_with_label(&decoded_bytes, charset) {
+            Ok(text) => out.push_str(&text),
+            Err(_) => out.push_str(payload),
+        }
+
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
 pub fn clean_invalid_xml_chars(input: &str) -> String {
     input
         .chars()
@@ -129,22 +405,202 @@ pub fn remove_dtd(xml: &str) -> String {
         xml.to_string()
     }
 }
-pub fn fix_html_entities(s: &str) -> String {
-    s.replace("&nbsp;", "\u{00A0}")
-     .replace("&lt;", "<")
-     .replace("&gt;", ">")
-     .replace("&amp;", "&")
-     .replace("&quot;", "\"")
-     .replace("&apos;", "'")
+// Longest WHATWG named reference (`CounterClockwiseContourIntegral;`) is 32
+// chars including its `;`; bound the scan so a stray `&` in running text can't force a long
+// linear search.
+const MAX_ENTITY_NAME_LEN: usize = 33;
+
+// Decodes HTML5 character references: numeric decimal (`&#169;`), numeric
+// hex (`&#xA9;`), and named (`&copy;`) - `name_to_codepoints` is the source
+// of the named set. Unknown/incomplete sequences (no terminating `;` within
+// `MAX_ENTITY_NAME_LEN`, or a name with no entry) are left untouched so code
+// samples with a bare `&` aren't corrupted.
+pub fn decode_html_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let tail = &rest[amp + 1..];
+
+        if let Some(digits) = tail.strip_prefix('#') {
+            if let Some((ch, consumed)) = decode_numeric_reference(digits) {
+                out.push(ch);
+                rest = &digits[consumed..];
+                continue;
+            }
+        } else if let Some((text, consumed)) = decode_named_reference(tail) {
+            out.push_str(&text);
+            rest = &tail[consumed..];
+            continue;
+        }
+
+        out.push('&');
+        rest = tail;
+    }
+    out.push_str(rest);
+    out
+}
+
+// `digits` is the text right after `&#`. Returns the decoded char and how
+// many bytes of `digits` (including the `x`/`X` prefix and terminating `;`)
+// were consumed.
+fn decode_numeric_reference(digits: &str) -> Option<(char, usize)> {
+    let (number, hex) =
+        if let Some(h) = digits.strip_prefix('x').or_else(|| digits.strip_prefix('X')) { (h, true) }
+        else { (digits, false) };
+
+    let semi = number.find(';')?;
+    if semi == 0 || semi > 8 {
+        return None;
+    }
+    let code = &number[..semi];
+    if !code.chars().all(|c| c.is_digit(if hex { 16 } else { 10 })) {
+        return None;
+    }
+    let code_point = u32::from_str_radix(code, if hex { 16 } else { 10 }).ok()?;
+
+    let ch = match code_point {
+        0xD800..=0xDFFF | 0x110000.. => '\u{FFFD}',
+        _ => char::from_u32(code_point).unwrap_or('\u{FFFD}'),
+    };
+
+    let prefix_len = if hex { 1 } else { 0 };
+    Some((ch, prefix_len + semi + 1))
+}
+
+// `tail` is the text right after `&`. Returns the decoded reference and how
+// many bytes of `tail` (the name plus its terminating `;`) were consumed.
+fn decode_named_reference(tail: &str) -> Option<(String, usize)> {
+    let semi = tail.bytes().take(MAX_ENTITY_NAME_LEN + 1).position(|b| b == b';')?;
+    if semi == 0 {
+        return None;
+    }
+    let codepoints = name_to_codepoints().get(&tail[..semi])?;
+
+    let mut text = String::new();
+    for &cp in codepoints {
+        if cp != 0 {
+            text.push(char::from_u32(cp).unwrap_or('\u{FFFD}'));
+        }
+    }
+    Some((text, semi + 1))
+}
+
+fn name_to_codepoints() -> &'static HashMap<&'static str, [u32; 2]> {
+    static TABLE: OnceLock<HashMap<&'static str, [u32; 2]>> = OnceLock::new();
+    TABLE.get_or_init(|| HTML5_NAMED_REFERENCES.iter().copied().collect())
 }
-pub fn sanitize_xml(data: &[u8]) -> anyhow::Result<String> {
-    let raw_xml = safe_decode_bytes(data)?;
-    let raw_xml = raw_xml.trim();
+
+// Deliberately scoped down from the full WHATWG HTML5 named character
+// reference table (https://html.spec.whatwg.org/entities.json, ~2200
+// entries) to the legacy HTML4-era subset (Latin-1, Greek, general
+// punctuation and symbols) that covers the vast majority of real-world
+// documents, mapping entity name -> Unicode scalar value. Generating the
+// full table requires pulling the current entities.json rather than
+// transcribing ~2000 codepoints from memory, so this subset ships instead
+// of a best-effort full table that can't be verified against the source.
+// Newer/rarer named references (e.g. `&ImaginaryI;`, `&NotEqualTilde;`)
+// aren't in here and fall through undecoded - if that starts showing up
+// in real documents, regenerate this table from entities.json rather than
+// hand-adding entries. The second slot of the pair exists for the WHATWG
+// entries that expand to two scalar values, but no entry currently
+// shipped here uses it.
+static HTML5_NAMED_REFERENCES: &[(&str, [u32; 2])] = &[
+    ("amp", [0x0026, 0]), ("lt", [0x003C, 0]), ("gt", [0x003E, 0]),
+    ("quot", [0x0022, 0]), ("apos", [0x0027, 0]), ("nbsp", [0x00A0, 0]),
+    ("iexcl", [0x00A1, 0]), ("cent", [0x00A2, 0]), ("pound", [0x00A3, 0]),
+    ("curren", [0x00A4, 0]), ("yen", [0x00A5, 0]), ("brvbar", [0x00A6, 0]),
+    ("sect", [0x00A7, 0]), ("uml", [0x00A8, 0]), ("copy", [0x00A9, 0]),
+    ("ordf", [0x00AA, 0]), ("laquo", [0x00AB, 0]), ("not", [0x00AC, 0]),
+    ("shy", [0x00AD, 0]), ("reg", [0x00AE, 0]), ("macr", [0x00AF, 0]),
+    ("deg", [0x00B0, 0]), ("plusmn", [0x00B1, 0]), ("sup2", [0x00B2, 0]),
+    ("sup3", [0x00B3, 0]), ("acute", [0x00B4, 0]), ("micro", [0x00B5, 0]),
+    ("para", [0x00B6, 0]), ("middot", [0x00B7, 0]), ("cedil", [0x00B8, 0]),
+    ("sup1", [0x00B9, 0]), ("ordm", [0x00BA, 0]), ("raquo", [0x00BB, 0]),
+    ("frac14", [0x00BC, 0]), ("frac12", [0x00BD, 0]), ("frac34", [0x00BE, 0]),
+    ("iquest", [0x00BF, 0]), ("Agrave", [0x00C0, 0]), ("Aacute", [0x00C1, 0]),
+    ("Acirc", [0x00C2, 0]), ("Atilde", [0x00C3, 0]), ("Auml", [0x00C4, 0]),
+    ("Aring", [0x00C5, 0]), ("AElig", [0x00C6, 0]), ("Ccedil", [0x00C7, 0]),
+    ("Egrave", [0x00C8, 0]), ("Eacute", [0x00C9, 0]), ("Ecirc", [0x00CA, 0]),
+    ("Euml", [0x00CB, 0]), ("Igrave", [0x00CC, 0]), ("Iacute", [0x00CD, 0]),
+    ("Icirc", [0x00CE, 0]), ("Iuml", [0x00CF, 0]), ("ETH", [0x00D0, 0]),
+    ("Ntilde", [0x00D1, 0]), ("Ograve", [0x00D2, 0]), ("Oacute", [0x00D3, 0]),
+    ("Ocirc", [0x00D4, 0]), ("Otilde", [0x00D5, 0]), ("Ouml", [0x00D6, 0]),
+    ("times", [0x00D7, 0]), ("Oslash", [0x00D8, 0]), ("Ugrave", [0x00D9, 0]),
+    ("Uacute", [0x00DA, 0]), ("Ucirc", [0x00DB, 0]), ("Uuml", [0x00DC, 0]),
+    ("Yacute", [0x00DD, 0]), ("THORN", [0x00DE, 0]), ("szlig", [0x00DF, 0]),
+    ("agrave", [0x00E0, 0]), ("aacute", [0x00E1, 0]), ("acirc", [0x00E2, 0]),
+    ("atilde", [0x00E3, 0]), ("auml", [0x00E4, 0]), ("aring", [0x00E5, 0]),
+    ("aelig", [0x00E6, 0]), ("ccedil", [0x00E7, 0]), ("egrave", [0x00E8, 0]),
+    ("eacute", [0x00E9, 0]), ("ecirc", [0x00EA, 0]), ("euml", [0x00EB, 0]),
+    ("igrave", [0x00EC, 0]), ("iacute", [0x00ED, 0]), ("icirc", [0x00EE, 0]),
+    ("iuml", [0x00EF, 0]), ("eth", [0x00F0, 0]), ("ntilde", [0x00F1, 0]),
+    ("ograve", [0x00F2, 0]), ("oacute", [0x00F3, 0]), ("ocirc", [0x00F4, 0]),
+    ("otilde", [0x00F5, 0]), ("ouml", [0x00F6, 0]), ("divide", [0x00F7, 0]),
+    ("oslash", [0x00F8, 0]), ("ugrave", [0x00F9, 0]), ("uacute", [0x00FA, 0]),
+    ("ucirc", [0x00FB, 0]), ("uuml", [0x00FC, 0]), ("yacute", [0x00FD, 0]),
+    ("thorn", [0x00FE, 0]), ("yuml", [0x00FF, 0]), ("OElig", [0x0152, 0]),
+    ("oelig", [0x0153, 0]), ("Scaron", [0x0160, 0]), ("scaron", [0x0161, 0]),
+    ("Yuml", [0x0178, 0]), ("fnof", [0x0192, 0]), ("circ", [0x02C6, 0]),
+    ("tilde", [0x02DC, 0]), ("ensp", [0x2002, 0]), ("emsp", [0x2003, 0]),
+    ("thinsp", [0x2009, 0]), ("zwnj", [0x200C, 0]), ("zwj", [0x200D, 0]),
+    ("lrm", [0x200E, 0]), ("rlm", [0x200F, 0]), ("ndash", [0x2013, 0]),
+    ("mdash", [0x2014, 0]), ("lsquo", [0x2018, 0]), ("rsquo", [0x2019, 0]),
+    ("sbquo", [0x201A, 0]), ("ldquo", [0x201C, 0]), ("rdquo", [0x201D, 0]),
+    ("bdquo", [0x201E, 0]), ("dagger", [0x2020, 0]), ("Dagger", [0x2021, 0]),
+    ("bull", [0x2022, 0]), ("hellip", [0x2026, 0]), ("permil", [0x2030, 0]),
+    ("prime", [0x2032, 0]), ("Prime", [0x2033, 0]), ("lsaquo", [0x2039, 0]),
+    ("rsaquo", [0x203A, 0]), ("oline", [0x203E, 0]), ("frasl", [0x2044, 0]),
+    ("euro", [0x20AC, 0]), ("trade", [0x2122, 0]), ("alefsym", [0x2135, 0]),
+    ("larr", [0x2190, 0]), ("uarr", [0x2191, 0]), ("rarr", [0x2192, 0]),
+    ("darr", [0x2193, 0]), ("harr", [0x2194, 0]), ("crarr", [0x21B5, 0]),
+    ("forall", [0x2200, 0]), ("part", [0x2202, 0]), ("exist", [0x2203, 0]),
+    ("empty", [0x2205, 0]), ("nabla", [0x2207, 0]), ("isin", [0x2208, 0]),
+    ("notin", [0x2209, 0]), ("ni", [0x220B, 0]), ("prod", [0x220F, 0]),
+    ("sum", [0x2211, 0]), ("minus", [0x2212, 0]), ("lowast", [0x2217, 0]),
+    ("radic", [0x221A, 0]), ("prop", [0x221D, 0]), ("infin", [0x221E, 0]),
+    ("ang", [0x2220, 0]), ("and", [0x2227, 0]), ("or", [0x2228, 0]),
+    ("cap", [0x2229, 0]), ("cup", [0x222A, 0]), ("int", [0x222B, 0]),
+    ("there4", [0x2234, 0]), ("sim", [0x223C, 0]), ("cong", [0x2245, 0]),
+    ("asymp", [0x2248, 0]), ("ne", [0x2260, 0]), ("equiv", [0x2261, 0]),
+    ("le", [0x2264, 0]), ("ge", [0x2265, 0]), ("sub", [0x2282, 0]),
+    ("sup", [0x2283, 0]), ("nsub", [0x2284, 0]), ("sube", [0x2286, 0]),
+    ("supe", [0x2287, 0]), ("oplus", [0x2295, 0]), ("otimes", [0x2297, 0]),
+    ("perp", [0x22A5, 0]), ("sdot", [0x22C5, 0]), ("lceil", [0x2308, 0]),
+    ("rceil", [0x2309, 0]), ("lfloor", [0x230A, 0]), ("rfloor", [0x230B, 0]),
+    ("lArr", [0x21D0, 0]), ("uArr", [0x21D1, 0]), ("rArr", [0x21D2, 0]),
+    ("dArr", [0x21D3, 0]), ("hArr", [0x21D4, 0]), ("weierp", [0x2118, 0]),
+    ("image", [0x2111, 0]), ("real", [0x211C, 0]), ("lang", [0x27E8, 0]),
+    ("rang", [0x27E9, 0]),
+    ("loz", [0x25CA, 0]), ("spades", [0x2660, 0]), ("clubs", [0x2663, 0]),
+    ("hearts", [0x2665, 0]), ("diams", [0x2666, 0]), ("Alpha", [0x0391, 0]),
+    ("Beta", [0x0392, 0]), ("Gamma", [0x0393, 0]), ("Delta", [0x0394, 0]),
+    ("Epsilon", [0x0395, 0]), ("Zeta", [0x0396, 0]), ("Eta", [0x0397, 0]),
+    ("Theta", [0x0398, 0]), ("Iota", [0x0399, 0]), ("Kappa", [0x039A, 0]),
+    ("Lambda", [0x039B, 0]), ("Mu", [0x039C, 0]), ("Nu", [0x039D, 0]),
+    ("Xi", [0x039E, 0]), ("Omicron", [0x039F, 0]), ("Pi", [0x03A0, 0]),
+    ("Rho", [0x03A1, 0]), ("Sigma", [0x03A3, 0]), ("Tau", [0x03A4, 0]),
+    ("Upsilon", [0x03A5, 0]), ("Phi", [0x03A6, 0]), ("Chi", [0x03A7, 0]),
+    ("Psi", [0x03A8, 0]), ("Omega", [0x03A9, 0]), ("alpha", [0x03B1, 0]),
+    ("beta", [0x03B2, 0]), ("gamma", [0x03B3, 0]), ("delta", [0x03B4, 0]),
+    ("epsilon", [0x03B5, 0]), ("zeta", [0x03B6, 0]), ("eta", [0x03B7, 0]),
+    ("theta", [0x03B8, 0]), ("iota", [0x03B9, 0]), ("kappa", [0x03BA, 0]),
+    ("lambda", [0x03BB, 0]), ("mu", [0x03BC, 0]), ("nu", [0x03BD, 0]),
+    ("xi", [0x03BE, 0]), ("omicron", [0x03BF, 0]), ("pi", [0x03C0, 0]),
+    ("rho", [0x03C1, 0]), ("sigmaf", [0x03C2, 0]), ("sigma", [0x03C3, 0]),
+    ("tau", [0x03C4, 0]), ("upsilon", [0x03C5, 0]), ("phi", [0x03C6, 0]),
+    ("chi", [0x03C7, 0]), ("psi", [0x03C8, 0]), ("omega", [0x03C9, 0]),
+    ("thetasym", [0x03D1, 0]), ("upsih", [0x03D2, 0]), ("piv", [0x03D6, 0]),
+];
+pub fn sanitize_xml(data: &[u8], hint: Option<&str>) -> anyhow::Result<String> {
+    let decoded = safe_decode_bytes(data, hint);
+    let raw_xml = decoded.text.trim();
 
     let cleaned_raw_xml =
         if is_dtd(raw_xml) {
             let xml = remove_dtd(raw_xml);
-            let xml = fix_html_entities(&xml);
+            let xml = decode_html_entities(&xml);
             clean_invalid_xml_chars(&xml)
         }
         else {